@@ -0,0 +1,25 @@
+fn main() {
+    #[cfg(feature = "cbindgen")]
+    generate_header();
+}
+
+#[cfg(feature = "cbindgen")]
+fn generate_header() {
+    use std::env;
+    use std::path::PathBuf;
+
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let config = cbindgen::Config::from_root_or_default(&crate_dir);
+
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_crate(&crate_dir)
+        .with_language(cbindgen::Language::C)
+        .generate()
+        .expect("failed to generate bindings.h")
+        .write_to_file(out_dir.join("bindings.h"));
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -2,6 +2,10 @@ use std::ffi::{CStr, CString};
 use std::mem;
 use std::os::raw::{c_char, c_void};
 
+mod marshal;
+
+use marshal::Record;
+
 #[no_mangle]
 pub extern "C" fn add_one(x: i64) -> i64 {
     x + 1
@@ -21,16 +25,304 @@ pub extern "C" fn allocate(size: usize) -> *mut c_void {
     pointer as *mut c_void
 }
 
+/// # Safety
+///
+/// `pointer` must have been returned by `allocate` with the same `capacity`,
+/// and must not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn deallocate(pointer: *mut c_void, capacity: usize) {
+    let _ = Vec::from_raw_parts(pointer, 0, capacity);
+}
+
+/// Results must be released with `free_cstring`, never `deallocate`.
+///
+/// # Safety
+///
+/// `a` and `b` must be valid, NUL-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn concat(a: *mut c_char, b: *mut c_char) -> *mut c_char {
+    let a = CStr::from_ptr(a).to_bytes().to_vec();
+    let b = CStr::from_ptr(b).to_bytes().to_vec();
+    CString::from_vec_unchecked([a, b].concat().to_vec()).into_raw()
+}
+
+/// # Safety
+///
+/// `pointer` must have been returned by `concat` (or otherwise by
+/// `CString::into_raw`), and must not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn free_cstring(pointer: *mut c_char) {
+    let _ = CString::from_raw(pointer);
+}
+
+/// Binary-safe counterpart to `concat`: takes explicit lengths instead of
+/// relying on a NUL terminator, so embedded NULs survive the round trip.
+///
+/// # Safety
+///
+/// `a_ptr`/`b_ptr` must each point to at least `a_len`/`b_len` readable
+/// bytes.
 #[no_mangle]
-pub extern "C" fn deallocate(pointer: *mut c_void, capacity: usize) {
-    unsafe {
-        let _ = Vec::from_raw_parts(pointer, 0, capacity);
+pub unsafe extern "C" fn concat_bytes(
+    a_ptr: *const u8,
+    a_len: usize,
+    b_ptr: *const u8,
+    b_len: usize,
+) -> *mut Buffer {
+    let a = std::slice::from_raw_parts(a_ptr, a_len);
+    let b = std::slice::from_raw_parts(b_ptr, b_len);
+
+    pack(join([a, b]))
+}
+
+// Concatenates byte slices in order; kept separate from `pack` so the
+// joining logic can be exercised without crossing the FFI boundary. Takes an
+// iterator rather than a slice so callers like `concat_all` don't need to
+// collect their items into an intermediate `Vec` first.
+fn join<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut result = Vec::new();
+    for part in parts {
+        result.extend_from_slice(part);
     }
+    result
+}
+
+// Buffer carries an allocator-backed region's address and length as two
+// full-width fields, the same fix already applied to `Record`'s label: a
+// real 64-bit address does not fit in the 32 bits a packed `(ptr << 32 |
+// len)` u64 would leave for it, so `concat_bytes`/`concat_all` hand back a
+// pointer to one of these instead of a packed scalar. Release with
+// `free_buffer`, never `deallocate`.
+#[repr(C)]
+pub struct Buffer {
+    pub ptr: u64,
+    pub len: u64,
 }
 
+// `shrink_to_fit` keeps capacity equal to length, since `len` is the only
+// size `free_buffer` has to rebuild the `Vec` from.
+fn pack(mut bytes: Vec<u8>) -> *mut Buffer {
+    bytes.shrink_to_fit();
+    debug_assert_eq!(bytes.capacity(), bytes.len());
+
+    let len = bytes.len() as u64;
+    let ptr = bytes.as_ptr() as u64;
+    mem::forget(bytes);
+
+    Box::into_raw(Box::new(Buffer { ptr, len }))
+}
+
+/// # Safety
+///
+/// `buffer` must have been returned by `concat_bytes`/`concat_all`, and must
+/// not have been passed to `free_buffer`.
 #[no_mangle]
-pub extern "C" fn concat(a: *mut c_char, b: *mut c_char) -> *mut c_char {
-    let a = unsafe { CStr::from_ptr(a).to_bytes().to_vec() };
-    let b = unsafe { CStr::from_ptr(b).to_bytes().to_vec() };
-    unsafe { CString::from_vec_unchecked([a, b].concat().to_vec()) }.into_raw()
+pub unsafe extern "C" fn length(buffer: *const Buffer) -> u64 {
+    (&*buffer).len
+}
+
+/// # Safety
+///
+/// `buffer` must have been returned by `concat_bytes`/`concat_all`, and must
+/// not have been passed to `free_buffer`.
+#[no_mangle]
+pub unsafe extern "C" fn data_ptr(buffer: *const Buffer) -> u64 {
+    (&*buffer).ptr
+}
+
+/// # Safety
+///
+/// `buffer` must have been returned by `concat_bytes`/`concat_all`, and must
+/// not be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn free_buffer(buffer: *mut Buffer) {
+    let boxed = Box::from_raw(buffer);
+
+    let ptr = boxed.ptr as *mut u8;
+    let len = boxed.len as usize;
+    let _ = Vec::from_raw_parts(ptr, len, len);
+}
+
+/// Joins `count` NUL-terminated C strings read from `items` in one call.
+///
+/// # Safety
+///
+/// `items` must point to `count` valid, NUL-terminated C strings.
+#[no_mangle]
+pub unsafe extern "C" fn concat_all(items: *const *const c_char, count: usize) -> *mut Buffer {
+    let items = std::slice::from_raw_parts(items, count);
+    let parts = items.iter().map(|&item| CStr::from_ptr(item).to_bytes());
+
+    pack(join(parts))
+}
+
+/// Results must be released with `free_record`, never `deallocate`.
+///
+/// # Safety
+///
+/// `label` must be a valid, NUL-terminated C string.
+#[no_mangle]
+pub unsafe extern "C" fn make_record(id: i64, label: *const c_char) -> *mut Record {
+    marshal::to_raw(marshal::new_record(id, label))
+}
+
+/// # Safety
+///
+/// `record` must have been returned by `make_record`, and must not be used
+/// again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn free_record(record: *mut Record) {
+    marshal::free_record(record);
+}
+
+/// # Safety
+///
+/// `record` must have been returned by `make_record`, and must not have been
+/// passed to `free_record`.
+#[no_mangle]
+pub unsafe extern "C" fn record_id(record: *const Record) -> i64 {
+    marshal::from_raw(record).id
+}
+
+/// # Safety
+///
+/// `record` must have been returned by `make_record`, and must not have been
+/// passed to `free_record`.
+#[no_mangle]
+pub unsafe extern "C" fn record_label_ptr(record: *const Record) -> u64 {
+    marshal::from_raw(record).label_ptr
+}
+
+/// # Safety
+///
+/// `record` must have been returned by `make_record`, and must not have been
+/// passed to `free_record`.
+#[no_mangle]
+pub unsafe extern "C" fn record_label_len(record: *const Record) -> u64 {
+    marshal::from_raw(record).label_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concat_then_free_cstring_round_trips() {
+        let a_ptr = CString::new("hello ").unwrap().into_raw();
+        let b_ptr = CString::new("world").unwrap().into_raw();
+
+        let joined = unsafe { concat(a_ptr, b_ptr) };
+        let text = unsafe { CStr::from_ptr(joined) }.to_str().unwrap();
+        assert_eq!(text, "hello world");
+
+        unsafe {
+            free_cstring(joined);
+            free_cstring(a_ptr);
+            free_cstring(b_ptr);
+        }
+    }
+
+    #[test]
+    fn join_concatenates_in_order() {
+        assert_eq!(join([b"hello ".as_slice(), b"world"]), b"hello world");
+    }
+
+    #[test]
+    fn join_preserves_embedded_nul() {
+        assert_eq!(join([b"foo\0bar".as_slice(), b"baz"]), b"foo\0barbaz");
+    }
+
+    #[test]
+    fn length_and_data_ptr_read_the_buffer_fields() {
+        // `ptr` is a synthetic, non-dereferenced address here, so free it by
+        // dropping the `Box` directly rather than via `free_buffer`, which
+        // would try to reconstruct a `Vec` from it.
+        let buffer = Box::into_raw(Box::new(Buffer {
+            ptr: 0x1234_5678_9abc,
+            len: 42,
+        }));
+
+        unsafe {
+            assert_eq!(data_ptr(buffer), 0x1234_5678_9abc);
+            assert_eq!(length(buffer), 42);
+            let _ = Box::from_raw(buffer);
+        }
+    }
+
+    // A real `Vec` allocation has an address well past `u32::MAX` on a
+    // 64-bit host, so this exercises the actual failure mode a packed
+    // 32-bit fat pointer would hit: `data_ptr`/`length` must hand back an
+    // address that still reads the right bytes, not a truncated one.
+    #[test]
+    fn concat_bytes_round_trips_through_a_real_allocation() {
+        let a = b"hello ";
+        let b = b"world";
+
+        unsafe {
+            let buffer = concat_bytes(a.as_ptr(), a.len(), b.as_ptr(), b.len());
+
+            let ptr = data_ptr(buffer) as *const u8;
+            let len = length(buffer) as usize;
+            assert!(ptr as u64 > u32::MAX as u64);
+
+            let bytes = std::slice::from_raw_parts(ptr, len);
+            assert_eq!(bytes, b"hello world");
+
+            free_buffer(buffer);
+        }
+    }
+
+    #[test]
+    fn join_many_parts_matches_expected_total() {
+        let result = join([
+            b"hello".as_slice(),
+            b"world",
+            b"foo",
+            b"bar123456",
+        ]);
+        assert_eq!(result, b"helloworldfoobar123456");
+    }
+
+    #[test]
+    fn concat_all_packs_exact_total_length() {
+        let items: Vec<CString> = ["hello", "world", "foo", "bar123456"]
+            .iter()
+            .map(|s| CString::new(*s).unwrap())
+            .collect();
+        let ptrs: Vec<*const c_char> = items.iter().map(|s| s.as_ptr()).collect();
+
+        unsafe {
+            let buffer = concat_all(ptrs.as_ptr(), ptrs.len());
+
+            assert_eq!(length(buffer), 22);
+
+            free_buffer(buffer);
+        }
+    }
+
+    #[test]
+    fn make_record_round_trips_id_and_label_len() {
+        let label = CString::new("hello").unwrap();
+
+        unsafe {
+            let record = make_record(7, label.as_ptr());
+
+            assert_eq!(record_id(record), 7);
+            assert_eq!(record_label_len(record), 5);
+
+            free_record(record);
+        }
+    }
+
+    #[test]
+    fn free_record_drops_the_box_and_an_empty_label() {
+        let label = CString::new("").unwrap();
+
+        unsafe {
+            let record = make_record(99, label.as_ptr());
+            assert_eq!(record_id(record), 99);
+
+            free_record(record);
+        }
+    }
 }
@@ -0,0 +1,57 @@
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::c_char;
+
+// `label_ptr`/`label_len` describe a separate allocation holding the label
+// bytes. The address is stored in full rather than packed into 32 bits of a
+// single `u64` together with the length, which would truncate it on a 64-bit
+// host; `Buffer` in lib.rs does the same for `concat_bytes`'s return value.
+#[repr(C)]
+pub struct Record {
+    pub id: i64,
+    pub label_ptr: u64,
+    pub label_len: u64,
+}
+
+// Box's allocation has `Record`'s own layout (size 24, align 8), which
+// `deallocate`'s `Vec<c_void>` reconstruction does not match. Records must
+// be released with `free_record`, never `deallocate`.
+pub fn to_raw(record: Record) -> *mut Record {
+    Box::into_raw(Box::new(record))
+}
+
+// Reads a `Record` without taking ownership of `pointer`.
+pub fn from_raw(pointer: *const Record) -> Record {
+    let record = unsafe { &*pointer };
+    Record {
+        id: record.id,
+        label_ptr: record.label_ptr,
+        label_len: record.label_len,
+    }
+}
+
+// Reclaims a `Record` produced by `to_raw`, along with the separate
+// allocation described by its `label_ptr`/`label_len` fields.
+pub fn free_record(pointer: *mut Record) {
+    let boxed = unsafe { Box::from_raw(pointer) };
+
+    let ptr = boxed.label_ptr as *mut u8;
+    let len = boxed.label_len as usize;
+    let _ = unsafe { Vec::from_raw_parts(ptr, len, len) };
+}
+
+pub fn new_record(id: i64, label: *const c_char) -> Record {
+    let mut bytes = unsafe { CStr::from_ptr(label).to_bytes() }.to_vec();
+    bytes.shrink_to_fit();
+    debug_assert_eq!(bytes.capacity(), bytes.len());
+
+    let label_len = bytes.len() as u64;
+    let label_ptr = bytes.as_ptr() as u64;
+    mem::forget(bytes);
+
+    Record {
+        id,
+        label_ptr,
+        label_len,
+    }
+}